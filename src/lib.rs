@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::task::{self, JoinHandle};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use message::{Response, Request};
+use rosc::{OscMessage, OscPacket};
+
+use lay::{Operations, gates::{CliffordGate, SGate, TGate, RXGate, RYGate, RZGate}};
+use lay_simulator_blueqat::{BlueqatSimulator, BlueqatOperations};
+
+pub mod message;
+pub mod transport;
+
+use transport::{Transport, TransportKind};
+
+type Qubit = <BlueqatOperations as Operations>::Qubit;
+
+pub const QUEUE_LEN: usize = 100;
+pub const OSC_BUF_LEN: usize = 1000;
+/// How long a client session may sit idle before the runner drops it.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Factory used to construct the per-session simulator. Exposing it lets
+/// downstream crates inject a mock backend for integration tests.
+pub type SimulatorFactory = Arc<dyn Fn() -> BlueqatSimulator + Send + Sync>;
+
+/// Runtime configuration for a [`Server`].
+///
+/// There is deliberately no separate sender/outgoing address: every
+/// [`Response`] is routed back to the [`SocketAddr`] its request came from,
+/// so one bound socket serves both directions and a second bind address
+/// would go unused.
+pub struct ServerConfig {
+    /// Address the server's one transport binds to, for both receiving
+    /// requests and sending responses back to their source address.
+    pub rx_addr: SocketAddr,
+    /// Which wire transport to use.
+    pub transport: TransportKind,
+    /// Capacity of the internal request/response channels.
+    pub queue_len: usize,
+    /// Size of the OSC receive buffer.
+    pub osc_buf_len: usize,
+    /// Factory that produces a fresh simulator for each new client session.
+    pub simulator_factory: SimulatorFactory,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            rx_addr: "0.0.0.0:0".parse().unwrap(),
+            transport: TransportKind::Udp,
+            queue_len: QUEUE_LEN,
+            osc_buf_len: OSC_BUF_LEN,
+            simulator_factory: Arc::new(|| BlueqatSimulator::new().unwrap()),
+        }
+    }
+}
+
+/// The OSC-to-Blueqat bridge server.
+///
+/// Build one with [`Server::new`] and start it with [`Server::run`], which
+/// spawns the receiver/runner/sender trio and hands back a [`ServerHandle`].
+pub struct Server {
+    config: ServerConfig,
+}
+
+impl Server {
+    pub fn new(config: ServerConfig) -> Self {
+        Server { config }
+    }
+
+    /// Bind the transport and spawn the three loops.
+    pub async fn run(self) -> anyhow::Result<ServerHandle> {
+        let ServerConfig { rx_addr, transport, queue_len, osc_buf_len, simulator_factory } =
+            self.config;
+        info!("Server: Binding {:?} transport to {}", transport, rx_addr);
+        let transport = Transport::bind(transport, rx_addr).await?;
+        let (ops_tx, ops_rx) = mpsc::channel(queue_len);
+        let (result_tx, result_rx) = mpsc::channel(queue_len);
+        let sender = task::spawn(sender_loop(transport.clone(), result_rx));
+        let runner = task::spawn(runner_loop(ops_rx, result_tx, simulator_factory));
+        let receiver = task::spawn(receiver_loop(transport, ops_tx, osc_buf_len));
+        Ok(ServerHandle { sender, runner, receiver })
+    }
+}
+
+/// Handle to a running [`Server`], exposing the task join handles and a
+/// shutdown trigger.
+pub struct ServerHandle {
+    pub sender: JoinHandle<anyhow::Result<()>>,
+    pub runner: JoinHandle<anyhow::Result<()>>,
+    pub receiver: JoinHandle<anyhow::Result<()>>,
+}
+
+impl ServerHandle {
+    /// Abort all three loops, dropping the client sessions and their
+    /// simulators; a UDP socket is closed with them. The QUIC endpoint and the
+    /// WebSocket listener run in their own accept tasks, which this does not
+    /// stop, so those stay bound.
+    pub fn shutdown(&self) {
+        self.receiver.abort();
+        self.runner.abort();
+        self.sender.abort();
+    }
+}
+
+/// Loop for sending response to client.
+///
+/// Responses are routed back to the address the originating request came from,
+/// so concurrent clients each receive only their own results.
+async fn sender_loop(tx: Transport, mut chan_rx: mpsc::Receiver<(SocketAddr, Response)>) -> anyhow::Result<()> {
+    while let Some((addr, msg)) = chan_rx.recv().await {
+        info!("sender_loop: Received from channel: {:?}", msg);
+        let packet = rosc::encoder::encode(&OscPacket::Message(OscMessage::from(&msg)))
+            .map_err(|e| anyhow!("{:?}", e))?;
+        info!("sender_loop: Encoded packet (len={}): {:?}", packet.len(), packet);
+        info!("sender_loop: Sending to {}...", addr);
+        if let Err(e) = tx.send_to(&packet, addr).await {
+            warn!("sender_loop: failed to send to {}: {:?}", addr, e);
+            continue;
+        }
+        info!("sender_loop: Sent.");
+    }
+    bail!("sender_loop: unexpected finished");
+}
+
+/// Loop for receiving request from client.
+async fn receiver_loop(rx: Transport, chan_tx: mpsc::Sender<(SocketAddr, Request)>, osc_buf_len: usize) -> anyhow::Result<()> {
+    let mut buf = vec![0; osc_buf_len];
+    loop {
+        info!("receiver_loop: Receiving...");
+        let (len, src) = rx.recv_from(&mut buf).await?;
+        info!("receiver_loop: Received. len={}, bytes={:?}", len, &buf[..len]);
+        let packet = rosc::decoder::decode(&buf[..len]);
+        let packet = match packet {
+            Ok(inner) => inner,
+            Err(e) => {
+                warn!("receiver_loop: OSC Error {:?}", e);
+                continue;
+            }
+        };
+        info!("receiver_loop: OSC Message: {:?}", packet);
+        let msg = match Request::try_from(packet) {
+            Ok(msg) => msg,
+            Err(e) => {
+                warn!("receiver_loop: {:?}", e);
+                continue;
+            }
+        };
+        info!("receiver_loop: Message: {:?}", msg);
+        chan_tx.send((src, msg)).await?;
+    }
+}
+
+/// Apply a single non-measuring gate request to the accumulated circuit.
+fn apply_unitary(ops: &mut BlueqatOperations, req: &Request) {
+    match req {
+        Request::X(_, n) => ops.x(*n as Qubit),
+        Request::Y(_, n) => ops.y(*n as Qubit),
+        Request::Z(_, n) => ops.z(*n as Qubit),
+        Request::H(_, n) => ops.h(*n as Qubit),
+        Request::S(_, n) => ops.s(*n as Qubit),
+        Request::Sdg(_, n) => ops.sdg(*n as Qubit),
+        Request::T(_, n) => ops.t(*n as Qubit),
+        Request::Tdg(_, n) => ops.tdg(*n as Qubit),
+        Request::RX(_, n, theta) => ops.rx(*theta, *n as Qubit),
+        Request::RY(_, n, theta) => ops.ry(*theta, *n as Qubit),
+        Request::RZ(_, n, theta) => ops.rz(*theta, *n as Qubit),
+        Request::CX(_, n1, _, n2) => ops.cx(*n1 as Qubit, *n2 as Qubit),
+        other => warn!("apply_unitary: not a unitary gate: {:?}", other),
+    }
+}
+
+/// Per-client simulation state: an independent register, the accumulated
+/// circuit not yet measured, and a scratch buffer for classical results.
+struct Session {
+    sim: BlueqatSimulator,
+    ops: BlueqatOperations,
+    result: String,
+    last_active: Instant,
+}
+
+impl Session {
+    fn new(factory: &SimulatorFactory, now: Instant) -> Self {
+        let sim = factory();
+        Session { sim, ops: fresh_ops(), result: String::new(), last_active: now }
+    }
+}
+
+/// A freshly initialized, empty circuit, ready to accumulate gates.
+fn fresh_ops() -> BlueqatOperations {
+    let mut ops = BlueqatOperations::new();
+    ops.initialize();
+    ops
+}
+
+/// Evict sessions that have been idle longer than [`SESSION_IDLE_TIMEOUT`] so
+/// they release their backing simulator.
+fn evict_stale(sessions: &mut HashMap<SocketAddr, Session>, now: Instant) {
+    sessions.retain(|addr, s| {
+        let keep = now.duration_since(s.last_active) < SESSION_IDLE_TIMEOUT;
+        if !keep {
+            info!("runner_loop: Evicting idle session {}", addr);
+        }
+        keep
+    });
+}
+
+async fn runner_loop(mut ops_rx: mpsc::Receiver<(SocketAddr, Request)>, result_tx: mpsc::Sender<(SocketAddr, Response)>, factory: SimulatorFactory) -> anyhow::Result<()> {
+    let mut sessions: HashMap<SocketAddr, Session> = HashMap::new();
+    info!("runner_loop: Start");
+    while let Some((addr, msg)) = ops_rx.recv().await {
+        info!("runner_loop: Message received from channel. {} {:?}", addr, msg);
+        let now = Instant::now();
+        evict_stale(&mut sessions, now);
+        let session = sessions.entry(addr).or_insert_with(|| Session::new(&factory, now));
+        session.last_active = now;
+        match msg {
+            Request::Mz(_, n) => {
+                info!("runner_loop: Received Mz inst.");
+                session.ops.measure(n as Qubit, ());
+                info!("runner_loop: Calling blueqat...");
+                session.sim.send_receive(&session.ops, &mut session.result).await;
+                info!("runner_loop: Blueqat response: {}", session.result);
+                let bit = (session.result.as_bytes()[n as usize] - b'0') as i32;
+                result_tx.send((addr, Response::Mz(bit, 0.0))).await?;
+                session.ops = fresh_ops();
+                session.result.clear();
+            },
+            Request::Sample(_, mask, shots) => {
+                info!("runner_loop: Received Sample inst. mask={:#x}, shots={}", mask, shots);
+                for q in 0..32 {
+                    if mask & (1 << q) != 0 {
+                        session.ops.measure(q as Qubit, ());
+                    }
+                }
+                let mut counts: HashMap<String, u32> = HashMap::new();
+                for _ in 0..shots {
+                    session.sim.send_receive(&session.ops, &mut session.result).await;
+                    let bytes = session.result.as_bytes();
+                    let mut bitstring = String::new();
+                    for q in 0..32 {
+                        if mask & (1 << q) != 0 {
+                            bitstring.push(bytes[q as usize] as char);
+                        }
+                    }
+                    *counts.entry(bitstring).or_insert(0) += 1;
+                    session.result.clear();
+                }
+                info!("runner_loop: Histogram: {:?}", counts);
+                result_tx.send((addr, Response::Histogram(counts.into_iter().collect()))).await?;
+                session.ops = fresh_ops();
+            },
+            Request::Batch(reqs) => {
+                info!("runner_loop: Received Batch of {} requests.", reqs.len());
+                let mut measured = Vec::new();
+                for req in &reqs {
+                    match req {
+                        Request::Mz(_, n) => {
+                            session.ops.measure(*n as Qubit, ());
+                            measured.push(*n);
+                        },
+                        other => apply_unitary(&mut session.ops, other),
+                    }
+                }
+                if !measured.is_empty() {
+                    info!("runner_loop: Calling blueqat for batch...");
+                    session.sim.send_receive(&session.ops, &mut session.result).await;
+                    info!("runner_loop: Blueqat response: {}", session.result);
+                    for n in measured {
+                        let bit = (session.result.as_bytes()[n as usize] - b'0') as i32;
+                        result_tx.send((addr, Response::Mz(bit, 0.0))).await?;
+                    }
+                    session.ops = fresh_ops();
+                    session.result.clear();
+                }
+            },
+            other => apply_unitary(&mut session.ops, &other),
+        }
+    }
+    bail!("runner_loop unexpected exit");
+}
@@ -0,0 +1,279 @@
+use std::convert::TryFrom;
+
+use anyhow::{bail, ensure};
+
+use log::warn;
+use rosc::{OscMessage, OscPacket, OscType};
+
+/// A request decoded from an incoming OSC message.
+///
+/// Every gate, measurement and sampling variant keeps the OSC address it was
+/// decoded from as its first field, which the runner logs; [`Request::Batch`]
+/// holds the requests decoded from one bundle instead. Responses are routed by
+/// the client's socket address, not by this OSC address.
+#[derive(Debug, Clone)]
+pub enum Request {
+    X(String, u32),
+    Y(String, u32),
+    Z(String, u32),
+    H(String, u32),
+    S(String, u32),
+    Sdg(String, u32),
+    T(String, u32),
+    Tdg(String, u32),
+    RX(String, u32, f32),
+    RY(String, u32, f32),
+    RZ(String, u32, f32),
+    CX(String, u32, String, u32),
+    Mz(String, u32),
+    /// Run the accumulated circuit `shots` times, measuring the qubits
+    /// selected by the bit mask, and report the bitstring histogram.
+    Sample(String, u32, u32),
+    /// An ordered batch of requests decoded from a single OSC bundle and
+    /// applied to one circuit atomically.
+    Batch(Vec<Request>),
+}
+
+/// A response produced by the runner for a client.
+#[derive(Debug, Clone)]
+pub enum Response {
+    Mz(i32, f32),
+    /// Observed bitstring counts from a multi-shot sampling run.
+    Histogram(Vec<(String, u32)>),
+}
+
+fn arg_int(args: &[OscType], idx: usize) -> anyhow::Result<u32> {
+    match args.get(idx) {
+        Some(OscType::Int(i)) => Ok(*i as u32),
+        other => bail!("expected int argument at index {}, got {:?}", idx, other),
+    }
+}
+
+fn arg_float(args: &[OscType], idx: usize) -> anyhow::Result<f32> {
+    match args.get(idx) {
+        Some(OscType::Float(f)) => Ok(*f),
+        other => bail!("expected float argument at index {}, got {:?}", idx, other),
+    }
+}
+
+impl TryFrom<OscMessage> for Request {
+    type Error = anyhow::Error;
+
+    fn try_from(msg: OscMessage) -> anyhow::Result<Self> {
+        let OscMessage { addr, args } = msg;
+        Ok(match addr.as_str() {
+            "/x" => Request::X(addr.clone(), arg_int(&args, 0)?),
+            "/y" => Request::Y(addr.clone(), arg_int(&args, 0)?),
+            "/z" => Request::Z(addr.clone(), arg_int(&args, 0)?),
+            "/h" => Request::H(addr.clone(), arg_int(&args, 0)?),
+            "/s" => Request::S(addr.clone(), arg_int(&args, 0)?),
+            "/sdg" => Request::Sdg(addr.clone(), arg_int(&args, 0)?),
+            "/t" => Request::T(addr.clone(), arg_int(&args, 0)?),
+            "/tdg" => Request::Tdg(addr.clone(), arg_int(&args, 0)?),
+            "/rx" => Request::RX(addr.clone(), arg_int(&args, 0)?, arg_float(&args, 1)?),
+            "/ry" => Request::RY(addr.clone(), arg_int(&args, 0)?, arg_float(&args, 1)?),
+            "/rz" => Request::RZ(addr.clone(), arg_int(&args, 0)?, arg_float(&args, 1)?),
+            "/cx" => Request::CX(
+                addr.clone(),
+                arg_int(&args, 0)?,
+                addr.clone(),
+                arg_int(&args, 1)?,
+            ),
+            "/mz" => Request::Mz(addr.clone(), arg_int(&args, 0)?),
+            "/sample" => Request::Sample(
+                addr.clone(),
+                arg_int(&args, 0)?,
+                arg_int(&args, 1)?,
+            ),
+            other => bail!("unknown OSC address: {}", other),
+        })
+    }
+}
+
+impl TryFrom<OscPacket> for Request {
+    type Error = anyhow::Error;
+
+    /// Decode one OSC packet as received off the wire: a bare message, a
+    /// single-message bundle (kept on the original per-message path), or a
+    /// multi-message bundle submitted as one atomic [`Request::Batch`].
+    ///
+    /// A message that fails to decode inside a multi-message bundle is
+    /// logged and dropped rather than failing the whole batch, so one bad
+    /// message from a client can't take the rest of the bundle down with it.
+    fn try_from(packet: OscPacket) -> anyhow::Result<Self> {
+        match packet {
+            OscPacket::Message(msg) => Request::try_from(msg),
+            OscPacket::Bundle(bundle) => {
+                ensure!(!bundle.content.is_empty(), "Received empty bundle.");
+                if bundle.content.len() == 1 {
+                    let mut bundle = bundle;
+                    match bundle.content.pop().unwrap() {
+                        OscPacket::Message(msg) => Request::try_from(msg),
+                        OscPacket::Bundle(_) => bail!("Received nested bundle."),
+                    }
+                } else {
+                    let mut reqs = Vec::with_capacity(bundle.content.len());
+                    for content in bundle.content {
+                        match content {
+                            OscPacket::Message(msg) => match Request::try_from(msg) {
+                                Ok(req) => reqs.push(req),
+                                Err(e) => warn!("skipping undecodable message in bundle: {:?}", e),
+                            },
+                            OscPacket::Bundle(_) => warn!("skipping nested bundle in bundle"),
+                        }
+                    }
+                    Ok(Request::Batch(reqs))
+                }
+            }
+        }
+    }
+}
+
+impl From<&Response> for OscMessage {
+    fn from(resp: &Response) -> Self {
+        match resp {
+            Response::Mz(bit, prob) => OscMessage {
+                addr: "/mz".to_string(),
+                args: vec![OscType::Int(*bit), OscType::Float(*prob)],
+            },
+            Response::Histogram(counts) => {
+                let mut args = Vec::with_capacity(counts.len() * 2);
+                for (bitstring, count) in counts {
+                    args.push(OscType::String(bitstring.clone()));
+                    args.push(OscType::Int(*count as i32));
+                }
+                OscMessage {
+                    addr: "/histogram".to_string(),
+                    args,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rosc::OscBundle;
+
+    fn msg(addr: &str, args: Vec<OscType>) -> OscMessage {
+        OscMessage { addr: addr.to_string(), args }
+    }
+
+    fn bundle(content: Vec<OscPacket>) -> OscPacket {
+        OscPacket::Bundle(OscBundle { timetag: (0, 0).into(), content })
+    }
+
+    #[test]
+    fn parses_clifford_t_gates() {
+        assert!(matches!(
+            Request::try_from(msg("/s", vec![OscType::Int(1)])).unwrap(),
+            Request::S(_, 1)
+        ));
+        assert!(matches!(
+            Request::try_from(msg("/sdg", vec![OscType::Int(2)])).unwrap(),
+            Request::Sdg(_, 2)
+        ));
+        assert!(matches!(
+            Request::try_from(msg("/t", vec![OscType::Int(3)])).unwrap(),
+            Request::T(_, 3)
+        ));
+        assert!(matches!(
+            Request::try_from(msg("/tdg", vec![OscType::Int(4)])).unwrap(),
+            Request::Tdg(_, 4)
+        ));
+    }
+
+    #[test]
+    fn parses_parameterized_rotations() {
+        match Request::try_from(msg("/rx", vec![OscType::Int(0), OscType::Float(1.5)])).unwrap() {
+            Request::RX(_, 0, theta) => assert_eq!(theta, 1.5),
+            other => panic!("unexpected {:?}", other),
+        }
+        match Request::try_from(msg("/ry", vec![OscType::Int(1), OscType::Float(-0.5)])).unwrap() {
+            Request::RY(_, 1, theta) => assert_eq!(theta, -0.5),
+            other => panic!("unexpected {:?}", other),
+        }
+        match Request::try_from(msg("/rz", vec![OscType::Int(2), OscType::Float(0.75)])).unwrap() {
+            Request::RZ(_, 2, theta) => assert_eq!(theta, 0.75),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rotation_requires_float_argument() {
+        assert!(Request::try_from(msg("/rx", vec![OscType::Int(0), OscType::Int(1)])).is_err());
+    }
+
+    #[test]
+    fn parses_sample_request() {
+        match Request::try_from(msg("/sample", vec![OscType::Int(0x3), OscType::Int(100)])).unwrap() {
+            Request::Sample(_, mask, shots) => {
+                assert_eq!(mask, 0x3);
+                assert_eq!(shots, 100);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn histogram_response_encodes_bitstring_count_pairs() {
+        let resp = Response::Histogram(vec![("00".to_string(), 7), ("11".to_string(), 3)]);
+        let encoded = OscMessage::from(&resp);
+        assert_eq!(encoded.addr, "/histogram");
+        assert_eq!(
+            encoded.args,
+            vec![
+                OscType::String("00".to_string()),
+                OscType::Int(7),
+                OscType::String("11".to_string()),
+                OscType::Int(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_message_bundle_decodes_to_one_batch() {
+        let packet = bundle(vec![
+            OscPacket::Message(msg("/h", vec![OscType::Int(0)])),
+            OscPacket::Message(msg("/cx", vec![OscType::Int(0), OscType::Int(1)])),
+            OscPacket::Message(msg("/mz", vec![OscType::Int(0)])),
+        ]);
+        match Request::try_from(packet).unwrap() {
+            Request::Batch(reqs) => {
+                assert_eq!(reqs.len(), 3);
+                assert!(matches!(reqs[0], Request::H(_, 0)));
+                assert!(matches!(reqs[2], Request::Mz(_, 0)));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn single_message_bundle_decodes_to_bare_request() {
+        let packet = bundle(vec![OscPacket::Message(msg("/x", vec![OscType::Int(5)]))]);
+        assert!(matches!(Request::try_from(packet).unwrap(), Request::X(_, 5)));
+    }
+
+    #[test]
+    fn empty_bundle_is_rejected() {
+        assert!(Request::try_from(bundle(vec![])).is_err());
+    }
+
+    #[test]
+    fn bad_message_in_bundle_is_dropped_not_fatal() {
+        let packet = bundle(vec![
+            OscPacket::Message(msg("/h", vec![OscType::Int(0)])),
+            OscPacket::Message(msg("/unknown", vec![])),
+            OscPacket::Message(msg("/mz", vec![OscType::Int(0)])),
+        ]);
+        match Request::try_from(packet).unwrap() {
+            Request::Batch(reqs) => {
+                assert_eq!(reqs.len(), 2);
+                assert!(matches!(reqs[0], Request::H(_, 0)));
+                assert!(matches!(reqs[1], Request::Mz(_, 0)));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Wire transport for OSC packets.
+//!
+//! Both server loops speak in whole OSC packets and rely only on
+//! [`Transport::recv_from`] / [`Transport::send_to`]; the raw UDP path and the
+//! reliable QUIC path are interchangeable behind this enum. On QUIC every
+//! packet travels as a single length-prefixed frame on a unidirectional
+//! stream, so `rosc::decoder::decode` / `rosc::encoder::encode` keep seeing one
+//! `OscPacket` per call exactly as they do over UDP.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context};
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::{mpsc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::Message;
+
+use log::{info, warn};
+
+/// Which backend a [`Transport`] is built on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportKind {
+    Udp,
+    Quic,
+    WebSocket,
+}
+
+impl std::str::FromStr for TransportKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "udp" => Ok(TransportKind::Udp),
+            "quic" => Ok(TransportKind::Quic),
+            "ws" => Ok(TransportKind::WebSocket),
+            other => bail!("unknown transport {:?} (expected \"udp\", \"quic\" or \"ws\")", other),
+        }
+    }
+}
+
+/// A packet-oriented transport shared by the receiver and sender loops.
+#[derive(Clone)]
+pub enum Transport {
+    Udp(Arc<UdpSocket>),
+    Quic(Arc<QuicTransport>),
+    WebSocket(Arc<WebSocketTransport>),
+}
+
+impl Transport {
+    /// Bind a transport of the requested kind to `addr`.
+    pub async fn bind(kind: TransportKind, addr: SocketAddr) -> anyhow::Result<Self> {
+        match kind {
+            TransportKind::Udp => {
+                let sock = UdpSocket::bind(addr).await?;
+                Ok(Transport::Udp(Arc::new(sock)))
+            }
+            TransportKind::Quic => {
+                let quic = QuicTransport::bind(addr).await?;
+                Ok(Transport::Quic(Arc::new(quic)))
+            }
+            TransportKind::WebSocket => {
+                let ws = WebSocketTransport::bind(addr).await?;
+                Ok(Transport::WebSocket(Arc::new(ws)))
+            }
+        }
+    }
+
+    /// Receive one encoded OSC packet and the address it came from.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> anyhow::Result<(usize, SocketAddr)> {
+        match self {
+            Transport::Udp(sock) => Ok(sock.recv_from(buf).await?),
+            Transport::Quic(quic) => quic.recv_from(buf).await,
+            Transport::WebSocket(ws) => ws.recv_from(buf).await,
+        }
+    }
+
+    /// Send one encoded OSC packet to `addr`.
+    pub async fn send_to(&self, packet: &[u8], addr: SocketAddr) -> anyhow::Result<()> {
+        match self {
+            Transport::Udp(sock) => {
+                sock.send_to(packet, addr).await?;
+                Ok(())
+            }
+            Transport::Quic(quic) => quic.send_to(packet, addr).await,
+            Transport::WebSocket(ws) => ws.send_to(packet, addr).await,
+        }
+    }
+}
+
+/// A QUIC endpoint that delivers whole OSC packets.
+///
+/// Incoming frames from every accepted connection are funneled into a single
+/// channel so [`recv_from`](Self::recv_from) behaves like `UdpSocket::recv_from`,
+/// while the live connections are kept in `conns` so responses can be written
+/// back to the originating peer.
+pub struct QuicTransport {
+    conns: Arc<Mutex<HashMap<SocketAddr, quinn::Connection>>>,
+    inbound: Mutex<mpsc::Receiver<(SocketAddr, Vec<u8>)>>,
+}
+
+impl QuicTransport {
+    async fn bind(addr: SocketAddr) -> anyhow::Result<Self> {
+        let config = server_config()?;
+        let endpoint = quinn::Endpoint::server(config, addr)?;
+        let conns: Arc<Mutex<HashMap<SocketAddr, quinn::Connection>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let (tx, rx) = mpsc::channel(super::QUEUE_LEN);
+
+        let accept_conns = conns.clone();
+        tokio::spawn(async move {
+            while let Some(incoming) = endpoint.accept().await {
+                let conns = accept_conns.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = drive_connection(incoming, conns, tx).await {
+                        warn!("quic: connection ended: {:?}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(QuicTransport { conns, inbound: Mutex::new(rx) })
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> anyhow::Result<(usize, SocketAddr)> {
+        loop {
+            let (addr, frame) = self
+                .inbound
+                .lock()
+                .await
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("quic: all connections closed"))?;
+            let len = frame.len();
+            if len > buf.len() {
+                warn!("quic: dropping frame of {} bytes from {} (buffer is {})", len, addr, buf.len());
+                continue;
+            }
+            buf[..len].copy_from_slice(&frame);
+            return Ok((len, addr));
+        }
+    }
+
+    async fn send_to(&self, packet: &[u8], addr: SocketAddr) -> anyhow::Result<()> {
+        let conn = {
+            let conns = self.conns.lock().await;
+            conns.get(&addr).cloned()
+        };
+        let conn = match conn {
+            Some(conn) => conn,
+            None => {
+                warn!("quic: dropping response for {}: no live connection", addr);
+                return Ok(());
+            }
+        };
+        let mut stream = conn.open_uni().await?;
+        stream.write_all(&(packet.len() as u32).to_be_bytes()).await?;
+        stream.write_all(packet).await?;
+        stream.finish()?;
+        Ok(())
+    }
+}
+
+/// Read length-prefixed frames off every stream of one connection and forward
+/// them, tagged with the peer address, to the shared inbound channel.
+async fn drive_connection(
+    incoming: quinn::Incoming,
+    conns: Arc<Mutex<HashMap<SocketAddr, quinn::Connection>>>,
+    tx: mpsc::Sender<(SocketAddr, Vec<u8>)>,
+) -> anyhow::Result<()> {
+    let conn = incoming.await?;
+    let addr = conn.remote_address();
+    info!("quic: accepted connection from {}", addr);
+    conns.lock().await.insert(addr, conn.clone());
+    let result = loop {
+        let mut recv = match conn.accept_uni().await {
+            Ok(recv) => recv,
+            Err(e) => break Err(anyhow::Error::from(e)),
+        };
+        let mut len_bytes = [0u8; 4];
+        if let Err(e) = recv.read_exact(&mut len_bytes).await {
+            break Err(e).context("quic: reading frame length");
+        }
+        let len = u32::from_be_bytes(len_bytes) as usize;
+        let frame = match recv.read_to_end(len).await {
+            Ok(frame) => frame,
+            Err(e) => break Err(anyhow::Error::from(e)),
+        };
+        if tx.send((addr, frame)).await.is_err() {
+            break Ok(());
+        }
+    };
+    conns.lock().await.remove(&addr);
+    result
+}
+
+/// A WebSocket listener that carries one OSC packet per binary frame.
+///
+/// Browser and sandboxed music-tool clients can't speak raw UDP, so this
+/// backend accepts WebSocket connections and bridges them onto the very same
+/// channels the UDP/QUIC paths use. Each connection gets its own outbound
+/// channel keyed by peer address so responses are written back over the right
+/// socket instead of being sent by datagram.
+pub struct WebSocketTransport {
+    conns: Arc<Mutex<HashMap<SocketAddr, mpsc::Sender<Vec<u8>>>>>,
+    inbound: Mutex<mpsc::Receiver<(SocketAddr, Vec<u8>)>>,
+}
+
+impl WebSocketTransport {
+    async fn bind(addr: SocketAddr) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let conns: Arc<Mutex<HashMap<SocketAddr, mpsc::Sender<Vec<u8>>>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let (tx, rx) = mpsc::channel(super::QUEUE_LEN);
+
+        let accept_conns = conns.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(pair) => pair,
+                    Err(e) => {
+                        warn!("ws: accept failed: {:?}", e);
+                        continue;
+                    }
+                };
+                let conns = accept_conns.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = drive_socket(stream, peer, conns, tx).await {
+                        warn!("ws: connection {} ended: {:?}", peer, e);
+                    }
+                });
+            }
+        });
+
+        Ok(WebSocketTransport { conns, inbound: Mutex::new(rx) })
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> anyhow::Result<(usize, SocketAddr)> {
+        loop {
+            let (addr, frame) = self
+                .inbound
+                .lock()
+                .await
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("ws: all connections closed"))?;
+            let len = frame.len();
+            if len > buf.len() {
+                warn!("ws: dropping frame of {} bytes from {} (buffer is {})", len, addr, buf.len());
+                continue;
+            }
+            buf[..len].copy_from_slice(&frame);
+            return Ok((len, addr));
+        }
+    }
+
+    async fn send_to(&self, packet: &[u8], addr: SocketAddr) -> anyhow::Result<()> {
+        let out = {
+            let conns = self.conns.lock().await;
+            conns.get(&addr).cloned()
+        };
+        let out = out.ok_or_else(|| anyhow!("ws: no live connection for {}", addr))?;
+        out.send(packet.to_vec()).await.map_err(|_| anyhow!("ws: connection {} closed", addr))?;
+        Ok(())
+    }
+}
+
+/// Accept one WebSocket connection, forwarding inbound binary frames to the
+/// shared channel and writing outbound frames from the per-connection channel.
+async fn drive_socket(
+    stream: tokio::net::TcpStream,
+    peer: SocketAddr,
+    conns: Arc<Mutex<HashMap<SocketAddr, mpsc::Sender<Vec<u8>>>>>,
+    tx: mpsc::Sender<(SocketAddr, Vec<u8>)>,
+) -> anyhow::Result<()> {
+    let ws = tokio_tungstenite::accept_async(stream).await?;
+    info!("ws: accepted connection from {}", peer);
+    let (mut write, mut read) = ws.split();
+
+    let (out_tx, mut out_rx) = mpsc::channel::<Vec<u8>>(super::QUEUE_LEN);
+    conns.lock().await.insert(peer, out_tx);
+
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = out_rx.recv().await {
+            if write.send(Message::Binary(frame)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(msg) = read.next().await {
+        // Collapsing this into the match arm (as clippy suggests) would move `data`
+        // into a pattern guard, which isn't allowed.
+        #[allow(clippy::collapsible_match)]
+        match msg? {
+            Message::Binary(data) => {
+                if tx.send((peer, data)).await.is_err() {
+                    break;
+                }
+            }
+            Message::Close(_) => break,
+            // Ignore text/ping/pong frames; the protocol is binary OSC packets.
+            _ => {}
+        }
+    }
+
+    conns.lock().await.remove(&peer);
+    writer.abort();
+    Ok(())
+}
+
+/// Build a QUIC server config backed by a freshly generated self-signed
+/// certificate. OSC traffic here is trusted LAN livecoding, so peer
+/// authentication is intentionally left to the deployment.
+fn server_config() -> anyhow::Result<quinn::ServerConfig> {
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+            .map_err(|e| anyhow!("{:?}", e))?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+    let chain = vec![cert.der().clone()];
+    Ok(quinn::ServerConfig::with_single_cert(chain, key)?)
+}